@@ -0,0 +1,96 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{AppState, Error};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct Claims {
+    pub(crate) sub: String,
+    pub(crate) iat: usize,
+    pub(crate) exp: usize,
+}
+
+/// Guards mutating routes: rejects with 401 unless the request carries a
+/// valid, unexpired `Authorization: Bearer` token signed with `JWT_SECRET`.
+#[async_trait]
+impl FromRequestParts<AppState> for Claims {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(Error::Unauthorized)?;
+
+        let claims = decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(state.jwt_secret.as_bytes()),
+            &Validation::new(Algorithm::HS256),
+        )
+        .map_err(|_| Error::Unauthorized)?
+        .claims;
+
+        Ok(claims)
+    }
+}
+
+/// Verifies a caller-supplied secret against `TOKEN_ISSUER_SECRET` in constant time,
+/// so `/auth/token` cannot be used by anonymous callers to mint themselves write access.
+pub(crate) fn verify_issuer_secret(candidate: &str, expected: &str) -> bool {
+    let candidate = candidate.as_bytes();
+    let expected = expected.as_bytes();
+
+    if candidate.len() != expected.len() {
+        return false;
+    }
+
+    candidate
+        .iter()
+        .zip(expected.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// Issues a signed token for `user_id`, valid for `jwt_maxage` minutes.
+pub(crate) fn generate_token(user_id: &str, jwt_secret: &str, jwt_maxage: i64) -> Result<String, Error> {
+    let now = OffsetDateTime::now_utc();
+    let iat = now.unix_timestamp() as usize;
+    let exp = (now + time::Duration::minutes(jwt_maxage)).unix_timestamp() as usize;
+
+    let claims = Claims {
+        sub: user_id.to_string(),
+        iat,
+        exp,
+    };
+
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt_secret.as_bytes()))
+        .map_err(|_| Error::Validation("failed to sign token".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_issuer_secret_accepts_matching_secret() {
+        assert!(verify_issuer_secret("shared-secret", "shared-secret"));
+    }
+
+    #[test]
+    fn verify_issuer_secret_rejects_mismatched_secret() {
+        assert!(!verify_issuer_secret("wrong-secret", "shared-secret"));
+    }
+
+    #[test]
+    fn verify_issuer_secret_rejects_different_length() {
+        assert!(!verify_issuer_secret("short", "much-longer-secret"));
+    }
+}
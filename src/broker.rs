@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, error, warn};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use sqlx::{Pool, Postgres};
+
+use crate::{save_order_to_db, Order, SharedState};
+
+/// Tracks whether the consumer currently holds a live broker connection, so
+/// `/readyz` can report it without reaching into the consumer's reconnect loop.
+pub(crate) type BrokerStatus = Arc<AtomicBool>;
+
+fn parse_broker_url(url: &str) -> (String, u16) {
+    let without_scheme = url.splitn(2, "://").last().unwrap_or(url);
+    match without_scheme.split_once(':') {
+        Some((host, port)) => (host.to_string(), port.parse().unwrap_or(1883)),
+        None => (without_scheme.to_string(), 1883),
+    }
+}
+
+/// Spawns a background task that subscribes to `topic` on `broker_url` and feeds
+/// incoming orders through the same persistence path as `handle_order`.
+/// Reconnects with backoff on broker disconnect instead of ever taking down the process.
+pub fn spawn_order_consumer(
+    db_pool: Arc<Pool<Postgres>>,
+    shared_state: SharedState,
+    broker_url: String,
+    topic: String,
+    status: BrokerStatus,
+) {
+    tokio::spawn(async move {
+        let (host, port) = parse_broker_url(&broker_url);
+        let mut backoff = Duration::from_secs(1);
+        const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+        loop {
+            let mut mqtt_options = MqttOptions::new("wb-rest-order-consumer", &host, port);
+            mqtt_options.set_keep_alive(Duration::from_secs(30));
+
+            let (client, mut event_loop) = AsyncClient::new(mqtt_options, 10);
+            if let Err(e) = client.subscribe(&topic, QoS::AtLeastOnce).await {
+                error!("Failed to subscribe to {}: {:?}", topic, e);
+                status.store(false, Ordering::Relaxed);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+
+            debug!("Subscribed to broker topic {} at {}:{}", topic, host, port);
+
+            loop {
+                match event_loop.poll().await {
+                    // `subscribe()` only enqueues the request; the connection isn't
+                    // actually live until the broker acks the handshake here.
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        status.store(true, Ordering::Relaxed);
+                        backoff = Duration::from_secs(1);
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        handle_message(&db_pool, &shared_state, &publish.payload).await;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("Broker connection lost: {:?}, reconnecting", e);
+                        status.store(false, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            }
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    });
+}
+
+async fn handle_message(db_pool: &Pool<Postgres>, shared_state: &SharedState, payload: &[u8]) {
+    let order: Order = match serde_json::from_slice(payload) {
+        Ok(order) => order,
+        Err(e) => {
+            error!("Malformed order message on broker topic: {:?}", e);
+            return;
+        }
+    };
+
+    // Only cache the order once it's actually persisted, so the cache never
+    // reports an order as stored that the DB write failed to save.
+    match save_order_to_db(db_pool, &order).await {
+        Ok(newly_stored) => {
+            let mut state = shared_state.lock().await;
+            state.insert(order.order_uid.clone(), order.clone());
+            drop(state);
+
+            if newly_stored {
+                debug!("Stored order {} from broker", order.order_uid);
+            } else {
+                debug!("Order {} already stored, ignoring redelivery", order.order_uid);
+            }
+        }
+        Err(e) => error!("Failed to save broker-sourced order: {:?}", e),
+    }
+}
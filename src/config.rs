@@ -0,0 +1,42 @@
+use std::env;
+use std::net::SocketAddr;
+
+/// Centralizes the environment-derived settings that used to be scattered
+/// `env::var` calls across `main`, with sane defaults for everything but
+/// the secrets the service cannot safely guess.
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+    pub(crate) database_url: String,
+    pub(crate) bind_addr: SocketAddr,
+    pub(crate) db_max_connections: u32,
+    pub(crate) broker_url: String,
+    pub(crate) order_topic: String,
+    pub(crate) jwt_secret: String,
+    pub(crate) jwt_maxage: i64,
+    pub(crate) token_issuer_secret: String,
+}
+
+impl Config {
+    pub(crate) fn from_env() -> Self {
+        Config {
+            database_url: env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
+            bind_addr: env::var("BIND_ADDR")
+                .ok()
+                .and_then(|addr| addr.parse().ok())
+                .unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 3000))),
+            db_max_connections: env::var("DB_MAX_CONNECTIONS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_else(|| num_cpus::get() as u32),
+            broker_url: env::var("BROKER_URL").unwrap_or_else(|_| "mqtt://127.0.0.1:1883".to_string()),
+            order_topic: env::var("ORDER_TOPIC").unwrap_or_else(|_| "orders".to_string()),
+            jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
+            jwt_maxage: env::var("JWT_MAXAGE")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(60),
+            token_issuer_secret: env::var("TOKEN_ISSUER_SECRET")
+                .expect("TOKEN_ISSUER_SECRET must be set"),
+        }
+    }
+}
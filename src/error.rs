@@ -0,0 +1,43 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use log::error;
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub(crate) enum Error {
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("validation error: {0}")]
+    Validation(String),
+
+    #[error("unauthorized")]
+    Unauthorized,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            Error::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Serialization(_) => StatusCode::BAD_REQUEST,
+            Error::Validation(_) => StatusCode::BAD_REQUEST,
+            Error::Unauthorized => StatusCode::UNAUTHORIZED,
+        };
+
+        if status == StatusCode::INTERNAL_SERVER_ERROR {
+            error!("{}", self);
+        }
+
+        (status, Json(ErrorBody { error: self.to_string() })).into_response()
+    }
+}
@@ -1,21 +1,32 @@
 use axum::{
-    extract::{Json, State},
-    response::{Html, IntoResponse},
+    extract::{Json, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
     routing::{get, post},
     Router,
 };
 
 use serde::{Deserialize, Serialize, Deserializer};
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
-use std::net::SocketAddr;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use std::env;
 use dotenv::dotenv;
 
-use log::{debug, error};
+use log::{debug, error, warn};
 use log4rs;
 
+mod auth;
+mod broker;
+mod config;
+mod error;
+
+use auth::Claims;
+use broker::BrokerStatus;
+use config::Config;
+use error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 struct Delivery {
     name: String,
@@ -68,7 +79,7 @@ where
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
-struct Order {
+pub(crate) struct Order {
     order_uid: String,
     track_number: String,
     entry: String,
@@ -91,12 +102,16 @@ use axum::extract::FromRef;
 
 #[derive(Clone)]
 struct AppState {
-    shared_state: Arc<Mutex<Option<Order>>>,
+    shared_state: SharedState,
     db_pool: Arc<Pool<Postgres>>,
+    jwt_secret: String,
+    jwt_maxage: i64,
+    token_issuer_secret: String,
+    broker_status: BrokerStatus,
 }
 
-impl FromRef<AppState> for Arc<Mutex<Option<Order>>> {
-    fn from_ref(app_state: &AppState) -> Arc<Mutex<Option<Order>>> {
+impl FromRef<AppState> for SharedState {
+    fn from_ref(app_state: &AppState) -> SharedState {
         app_state.shared_state.clone()
     }
 }
@@ -108,14 +123,14 @@ impl FromRef<AppState> for Arc<Pool<Postgres>> {
 }
 
 
-// Define shared state
-type SharedState = Arc<Mutex<Option<Order>>>;
+// Cache of every known order, keyed by order_uid, so a restart doesn't forget everything
+pub(crate) type SharedState = Arc<Mutex<HashMap<String, Order>>>;
 
 async fn init_orders_shema(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
     let create_table_sql = "
         CREATE TABLE IF NOT EXISTS orders (
             id SERIAL PRIMARY KEY,
-            order_uid VARCHAR NOT NULL,
+            order_uid VARCHAR NOT NULL UNIQUE,
             track_number VARCHAR NOT NULL,
             entry VARCHAR NOT NULL,
             delivery JSONB,
@@ -134,21 +149,55 @@ async fn init_orders_shema(pool: &Pool<Postgres>) -> Result<(), sqlx::Error> {
 
     sqlx::query(create_table_sql).execute(pool).await?;
 
+    // Pre-existing deployments may already hold duplicate order_uid rows from
+    // redelivery before this constraint existed; the ALTER below fails outright
+    // if we don't clear those first, keeping the earliest row per order_uid.
+    let dedupe_sql = "
+        DELETE FROM orders a
+        USING orders b
+        WHERE a.order_uid = b.order_uid
+          AND a.id > b.id;
+    ";
+
+    sqlx::query(dedupe_sql).execute(pool).await?;
+
+    // `CREATE TABLE IF NOT EXISTS` is a no-op on deployments where `orders` already
+    // exists, so the UNIQUE constraint above never lands there. Backfill it explicitly.
+    let add_unique_constraint_sql = "
+        DO $$
+        BEGIN
+            IF NOT EXISTS (
+                SELECT 1 FROM pg_constraint WHERE conname = 'orders_order_uid_key'
+            ) THEN
+                ALTER TABLE orders ADD CONSTRAINT orders_order_uid_key UNIQUE (order_uid);
+            END IF;
+        END $$;
+    ";
+
+    sqlx::query(add_unique_constraint_sql).execute(pool).await?;
+
     Ok(())
 }
 
-async fn save_order_to_db(pool: &Pool<Postgres>, order: &Order) -> Result<(), sqlx::Error> {
-    let delivery_json = serde_json::to_value(&order.delivery).unwrap();
-    let payment_json = serde_json::to_value(&order.payment).unwrap();
-    let items_json = serde_json::to_value(&order.items).unwrap();
+/// Persists `order`, ignoring redelivery of an already-stored `order_uid`.
+/// Returns `true` when the row was newly inserted, `false` when it was a duplicate.
+/// The transaction rolls back automatically if a serde/DB error is returned before commit.
+pub(crate) async fn save_order_to_db(pool: &Pool<Postgres>, order: &Order) -> Result<bool, Error> {
+    let delivery_json = serde_json::to_value(&order.delivery)?;
+    let payment_json = serde_json::to_value(&order.payment)?;
+    let items_json = serde_json::to_value(&order.items)?;
 
-    sqlx::query!(
+    let mut tx = pool.begin().await?;
+
+    let newly_stored = sqlx::query!(
         r#"
         INSERT INTO orders (
             order_uid, track_number, entry, delivery, payment, items, locale,
             internal_signature, customer_id, delivery_service, shardkey, sm_id,
             date_created, oof_shard
         ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        ON CONFLICT (order_uid) DO NOTHING
+        RETURNING order_uid
         "#,
         order.order_uid,
         order.track_number,
@@ -165,80 +214,339 @@ async fn save_order_to_db(pool: &Pool<Postgres>, order: &Order) -> Result<(), sq
         order.date_created,
         order.oof_shard
     )
-    .execute(pool)
+    .fetch_optional(&mut *tx)
+    .await?
+    .is_some();
+
+    tx.commit().await?;
+
+    Ok(newly_stored)
+}
+
+/// Rebuilds an `Order` from the flattened columns plus JSONB `delivery`/`payment`/`items`,
+/// the shape shared by `restore_cache_from_db` and the `/order/:order_uid` DB fallback.
+#[allow(clippy::too_many_arguments)]
+fn order_from_row(
+    order_uid: String,
+    track_number: String,
+    entry: String,
+    delivery: Option<serde_json::Value>,
+    payment: Option<serde_json::Value>,
+    items: Option<serde_json::Value>,
+    locale: String,
+    internal_signature: String,
+    customer_id: String,
+    delivery_service: String,
+    shardkey: String,
+    sm_id: String,
+    date_created: OffsetDateTime,
+    oof_shard: i32,
+) -> Option<Order> {
+    let delivery: Delivery = serde_json::from_value(delivery?).ok()?;
+    let payment: Payment = serde_json::from_value(payment?).ok()?;
+    let items: Vec<Item> = serde_json::from_value(items?).ok()?;
+
+    Some(Order {
+        order_uid,
+        track_number,
+        entry,
+        delivery,
+        payment,
+        items,
+        locale,
+        internal_signature,
+        customer_id,
+        delivery_service,
+        shardkey,
+        sm_id: sm_id.parse().unwrap_or_default(),
+        date_created,
+        oof_shard: oof_shard.to_string(),
+    })
+}
+
+/// Rebuilds the order cache from whatever is already stored in Postgres, so the
+/// service serves warm data immediately after a restart instead of starting empty.
+async fn restore_cache_from_db(pool: &Pool<Postgres>) -> Result<HashMap<String, Order>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT order_uid, track_number, entry, delivery, payment, items, locale,
+               internal_signature, customer_id, delivery_service, shardkey, sm_id,
+               date_created as "date_created!: OffsetDateTime", oof_shard as "oof_shard!: i32"
+        FROM orders
+        "#
+    )
+    .fetch_all(pool)
     .await?;
 
-    Ok(())
+    let mut cache = HashMap::with_capacity(rows.len());
+    for row in rows {
+        let order_uid = row.order_uid.clone();
+        let order = order_from_row(
+            row.order_uid,
+            row.track_number,
+            row.entry,
+            row.delivery,
+            row.payment,
+            row.items,
+            row.locale,
+            row.internal_signature,
+            row.customer_id,
+            row.delivery_service,
+            row.shardkey,
+            row.sm_id,
+            row.date_created,
+            row.oof_shard,
+        );
+
+        match order {
+            Some(order) => {
+                cache.insert(order_uid, order);
+            }
+            None => warn!("Skipping order {} with malformed JSONB columns", order_uid),
+        }
+    }
+
+    Ok(cache)
 }
 
 // Handler to receive the order
 async fn handle_order(
     State(app_state): State<AppState>,
+    _claims: Claims,
     Json(order): Json<Order>,
-) -> impl IntoResponse {
-    // Access the shared state
+) -> Result<impl IntoResponse, Error> {
+    // Save to the database first so the cache never reports an order as stored
+    // that didn't actually persist.
+    let newly_stored = save_order_to_db(&app_state.db_pool, &order).await?;
+
     let mut state = app_state.shared_state.lock().await;
-    *state = Some(order.clone());
+    state.insert(order.order_uid.clone(), order.clone());
+    drop(state);
 
-    // Save the order to the database
-    if let Err(e) = save_order_to_db(&app_state.db_pool, &order).await {
-        eprintln!("Failed to save order: {:?}", e);
-        return "Failed to save order".into_response();
+    println!("Received order: {:?}", order);
+    Ok(if newly_stored {
+        "Order received"
+    } else {
+        "Order already stored"
+    })
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    user_id: String,
+    client_secret: String,
+}
+
+#[derive(Serialize)]
+struct TokenResponse {
+    token: String,
+}
+
+// Issues a signed JWT for `user_id`, gated on a shared `TOKEN_ISSUER_SECRET`
+// so anonymous callers can't mint themselves a token and write orders.
+async fn issue_token(
+    State(app_state): State<AppState>,
+    Json(payload): Json<TokenRequest>,
+) -> Result<impl IntoResponse, Error> {
+    if !auth::verify_issuer_secret(&payload.client_secret, &app_state.token_issuer_secret) {
+        return Err(Error::Unauthorized);
     }
 
-    println!("Received order: {:?}", order);
-    "Order received".into_response()
+    let token = auth::generate_token(&payload.user_id, &app_state.jwt_secret, app_state.jwt_maxage)?;
+    Ok(Json(TokenResponse { token }))
+}
+
+// Process liveness: if this responds at all, the service is up
+async fn healthz() -> impl IntoResponse {
+    StatusCode::OK
+}
+
+#[derive(Serialize)]
+struct ReadinessBody {
+    database: &'static str,
+    broker_connected: bool,
+}
+
+// Readiness: confirms Postgres is reachable and reports broker-connection status
+async fn readyz(State(app_state): State<AppState>) -> impl IntoResponse {
+    let db_ok = sqlx::query("SELECT 1").execute(&*app_state.db_pool).await.is_ok();
+    let body = ReadinessBody {
+        database: if db_ok { "ok" } else { "unavailable" },
+        broker_connected: app_state.broker_status.load(Ordering::Relaxed),
+    };
+
+    if db_ok {
+        (StatusCode::OK, Json(body)).into_response()
+    } else {
+        error!("Readiness check failed: database unreachable");
+        (StatusCode::SERVICE_UNAVAILABLE, Json(body)).into_response()
+    }
 }
 
 async fn show_order(State(app_state): State<AppState>) -> impl IntoResponse {
     let state = app_state.shared_state.lock().await;
-    if let Some(order) = &*state {
-        let pretty_json = serde_json::to_string_pretty(order).unwrap();
+    if state.is_empty() {
+        Html("<p>No orders received yet.</p>".to_string())
+    } else {
+        let pretty_json = serde_json::to_string_pretty(&*state).unwrap();
         Html(format!("<pre>{}</pre>", pretty_json))
+    }
+}
+
+/// Renders an order as `application/json` when the client asks for it via `Accept`,
+/// falling back to the existing `<pre>`-wrapped HTML view for browsers.
+fn render_order(order: &Order, headers: &HeaderMap) -> Response {
+    let wants_json = headers
+        .get(axum::http::header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .map(|accept| accept.contains("application/json"))
+        .unwrap_or(false);
+
+    if wants_json {
+        Json(order.clone()).into_response()
     } else {
-        Html("<p>No order received yet.</p>".to_string())
+        let pretty_json = serde_json::to_string_pretty(order).unwrap();
+        Html(format!("<pre>{}</pre>", pretty_json)).into_response()
     }
 }
 
+fn order_not_found(order_uid: &str) -> Response {
+    (StatusCode::NOT_FOUND, format!("Order {} not found", order_uid)).into_response()
+}
+
+// Handler for looking up a single order: cache first, Postgres on a miss
+async fn get_order(
+    State(app_state): State<AppState>,
+    Path(order_uid): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    {
+        let state = app_state.shared_state.lock().await;
+        if let Some(order) = state.get(&order_uid) {
+            return Ok(render_order(order, &headers));
+        }
+    }
+
+    let row = sqlx::query!(
+        r#"
+        SELECT order_uid, track_number, entry, delivery, payment, items, locale,
+               internal_signature, customer_id, delivery_service, shardkey, sm_id,
+               date_created as "date_created!: OffsetDateTime", oof_shard as "oof_shard!: i32"
+        FROM orders
+        WHERE order_uid = $1
+        "#,
+        order_uid
+    )
+    .fetch_optional(&*app_state.db_pool)
+    .await;
+
+    let row = match row {
+        Ok(Some(row)) => row,
+        Ok(None) => return Ok(order_not_found(&order_uid)),
+        Err(e) => {
+            error!("Failed to look up order {}: {:?}", order_uid, e);
+            return Err(Error::Database(e));
+        }
+    };
+
+    let order = order_from_row(
+        row.order_uid,
+        row.track_number,
+        row.entry,
+        row.delivery,
+        row.payment,
+        row.items,
+        row.locale,
+        row.internal_signature,
+        row.customer_id,
+        row.delivery_service,
+        row.shardkey,
+        row.sm_id,
+        row.date_created,
+        row.oof_shard,
+    );
+
+    let order = match order {
+        Some(order) => order,
+        None => {
+            error!("Order {} stored with malformed JSONB columns", order_uid);
+            return Ok(order_not_found(&order_uid));
+        }
+    };
+
+    {
+        let mut state = app_state.shared_state.lock().await;
+        state.insert(order.order_uid.clone(), order.clone());
+    }
+
+    Ok(render_order(&order, &headers))
+}
+
 #[tokio::main]
 async fn main() {
     log4rs::init_file("src/resources/log4rs.yaml", Default::default()).unwrap();
 
     dotenv().ok();
-    
-    for (key, value) in env::vars() {
-        println!("{}: {}", key, value);
-    }
-    
+
+    let config = Config::from_env();
+
     // Initialize shared state
-    let shared_state = Arc::new(Mutex::new(None));
-    
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    
+    let shared_state: SharedState = Arc::new(Mutex::new(HashMap::new()));
+
     let pool = PgPoolOptions::new()
-        .connect(&db_url)
+        .max_connections(config.db_max_connections)
+        .connect(&config.database_url)
         .await
         .expect("Failed to create pool");
 
-    if let Err(e) = init_orders_shema(&pool).await {
-        eprintln!("Failed to create table orders: {}", e);
+    init_orders_shema(&pool)
+        .await
+        .expect("Failed to initialize orders schema");
+
+    match restore_cache_from_db(&pool).await {
+        Ok(cache) => {
+            debug!("Restored {} orders from the database", cache.len());
+            *shared_state.lock().await = cache;
+        }
+        Err(e) => error!("Failed to restore order cache from db: {}", e),
     }
 
+    let db_pool = Arc::new(pool);
+
+    let broker_status: BrokerStatus = Arc::new(AtomicBool::new(false));
+
     let app_state = AppState {
         shared_state: shared_state.clone(),
-        db_pool: Arc::new(pool),
+        db_pool: db_pool.clone(),
+        jwt_secret: config.jwt_secret.clone(),
+        jwt_maxage: config.jwt_maxage,
+        token_issuer_secret: config.token_issuer_secret.clone(),
+        broker_status: broker_status.clone(),
     };
 
-    // Build the application via routes 
+    // Consume orders arriving off the broker alongside the HTTP ingestion path
+    broker::spawn_order_consumer(
+        db_pool.clone(),
+        shared_state.clone(),
+        config.broker_url.clone(),
+        config.order_topic.clone(),
+        broker_status,
+    );
+
+    // Build the application via routes
     // 1. get order in pretty format
     // 2. accept post-request
     let app = Router::new()
         .route("/", get(show_order))
         .route("/order", post(handle_order))
+        .route("/order/:order_uid", get(get_order))
+        .route("/auth/token", post(issue_token))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
         .with_state(app_state);
 
     // Specify the address to run the server on
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
+    let addr = config.bind_addr;
     println!("Listening on {}", addr);
 
     debug!("log check");
@@ -250,3 +558,117 @@ async fn main() {
         .await
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_delivery_json() -> serde_json::Value {
+        serde_json::json!({
+            "name": "name",
+            "phone": "+10000000000",
+            "zip": "00000",
+            "city": "city",
+            "address": "address",
+            "region": "region",
+            "email": "a@example.com"
+        })
+    }
+
+    fn valid_payment_json() -> serde_json::Value {
+        serde_json::json!({
+            "transaction": "t",
+            "request_id": "r",
+            "currency": "USD",
+            "provider": "p",
+            "amount": 100,
+            "payment_dt": 1,
+            "bank": "b",
+            "delivery_cost": 10,
+            "goods_total": 90,
+            "custom_fee": 0
+        })
+    }
+
+    fn valid_items_json() -> serde_json::Value {
+        serde_json::json!([{
+            "chrt_id": 1,
+            "track_number": "tn",
+            "price": 10,
+            "rid": "rid",
+            "name": "name",
+            "sale": 0,
+            "size": "m",
+            "total_price": 10,
+            "nm_id": 1,
+            "brand": "brand",
+            "status": 1
+        }])
+    }
+
+    #[test]
+    fn order_from_row_rebuilds_a_valid_order() {
+        let order = order_from_row(
+            "uid".to_string(),
+            "tn".to_string(),
+            "entry".to_string(),
+            Some(valid_delivery_json()),
+            Some(valid_payment_json()),
+            Some(valid_items_json()),
+            "en".to_string(),
+            "sig".to_string(),
+            "cust".to_string(),
+            "svc".to_string(),
+            "shard".to_string(),
+            "1".to_string(),
+            OffsetDateTime::now_utc(),
+            1,
+        );
+
+        assert_eq!(order.expect("valid columns should rebuild").order_uid, "uid");
+    }
+
+    #[test]
+    fn order_from_row_rejects_malformed_delivery_json() {
+        let order = order_from_row(
+            "uid".to_string(),
+            "tn".to_string(),
+            "entry".to_string(),
+            Some(serde_json::json!({"not": "a delivery"})),
+            Some(valid_payment_json()),
+            Some(valid_items_json()),
+            "en".to_string(),
+            "sig".to_string(),
+            "cust".to_string(),
+            "svc".to_string(),
+            "shard".to_string(),
+            "1".to_string(),
+            OffsetDateTime::now_utc(),
+            1,
+        );
+
+        assert!(order.is_none());
+    }
+
+    #[test]
+    fn order_from_row_rejects_missing_jsonb_columns() {
+        let order = order_from_row(
+            "uid".to_string(),
+            "tn".to_string(),
+            "entry".to_string(),
+            None,
+            Some(valid_payment_json()),
+            Some(valid_items_json()),
+            "en".to_string(),
+            "sig".to_string(),
+            "cust".to_string(),
+            "svc".to_string(),
+            "shard".to_string(),
+            "1".to_string(),
+            OffsetDateTime::now_utc(),
+            1,
+        );
+
+        assert!(order.is_none());
+    }
+}